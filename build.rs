@@ -1,25 +1,233 @@
-use sha2::{Digest, Sha256};
+use fs4::FileExt;
 use std::io::Result;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fs};
-fn build_embree() -> Result<String> {
+
+#[path = "build/download.rs"]
+mod download;
+use download::{fetch, sha256sum, EMBREE_REPO_URL};
+
+/// Embree compile-time options controlled by cargo features, as
+/// `(CMake option, enabled, value in the prebuilt packages)`.
+const BUILD_OPTIONS: [(&str, bool, bool); 8] = [
+    (
+        "EMBREE_BACKFACE_CULLING",
+        cfg!(feature = "backface-culling"),
+        false,
+    ),
+    (
+        "EMBREE_BACKFACE_CULLING_CURVES",
+        cfg!(feature = "backface-culling-curves"),
+        false,
+    ),
+    (
+        "EMBREE_FILTER_FUNCTION",
+        cfg!(feature = "filter-function"),
+        true,
+    ),
+    (
+        "EMBREE_IGNORE_INVALID_RAYS",
+        cfg!(feature = "ignore-invalid-rays"),
+        false,
+    ),
+    (
+        "EMBREE_COMPACT_POLYS",
+        cfg!(feature = "compact-polys"),
+        false,
+    ),
+    ("EMBREE_MIN_WIDTH", cfg!(feature = "min-width"), false),
+    ("EMBREE_RAY_PACKETS", cfg!(feature = "ray-packets"), true),
+    ("EMBREE_RAY_MASK", cfg!(feature = "ray-mask"), true),
+];
+
+/// Writes `BUILD_CONFIG` to `$OUT_DIR/build_config.rs`, included by `src/config.rs`.
+fn write_build_config() {
+    let mut config = String::from("pub const BUILD_CONFIG: BuildConfig = BuildConfig {\n");
+    for (option, enabled, _) in BUILD_OPTIONS {
+        let field = option.trim_start_matches("EMBREE_").to_lowercase();
+        config += &format!("    {}: {},\n", field, enabled);
+    }
+    config += "};\n";
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("build_config.rs"), config).unwrap();
+}
+
+/// A oneTBB installation to build and link embree against.
+struct Tbb {
+    root: PathBuf,
+    lib_dirs: Vec<PathBuf>,
+}
+
+/// Library directories of a oneTBB install prefix, relative to `TBB_ROOT`.
+/// The oneTBB/oneAPI binary packages use the `intel64` layouts.
+const TBB_LIB_SUBDIRS: [&str; 6] = [
+    "lib",
+    "lib64",
+    "bin",
+    "lib/intel64/gcc4.8",
+    "lib/intel64/vc14",
+    "redist/intel64/vc14",
+];
+
+/// Whether `dir` holds the `tbb`/`tbb12` library itself (not tbbmalloc & co).
+fn has_tbb_lib(dir: &Path) -> bool {
+    let is_tbb = |name: &str| {
+        ["libtbb.", "libtbb12.", "tbb.", "tbb12."]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    };
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|entry| is_tbb(&entry.file_name().to_string_lossy()))
+        })
+        .unwrap_or(false)
+}
+
+fn find_tbb() -> Tbb {
+    println!("cargo:rerun-if-env-changed=TBB_ROOT");
+    if let Ok(root) = env::var("TBB_ROOT") {
+        let root = PathBuf::from(root);
+        let lib_dirs: Vec<PathBuf> = TBB_LIB_SUBDIRS
+            .iter()
+            .map(|subdir| root.join(subdir))
+            .filter(|dir| has_tbb_lib(dir))
+            .collect();
+        assert!(
+            !lib_dirs.is_empty(),
+            "Unable to find the tbb or tbb12 library under TBB_ROOT `{}`, searched {:?}",
+            root.display(),
+            TBB_LIB_SUBDIRS
+        );
+        return Tbb { root, lib_dirs };
+    }
+    let pkg_config = |variable: &str| -> Option<PathBuf> {
+        let output = Command::new("pkg-config")
+            .args([&format!("--variable={}", variable), "tbb"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let value = String::from_utf8(output.stdout).ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(value))
+        }
+    };
+    match (pkg_config("prefix"), pkg_config("libdir")) {
+        (Some(root), Some(lib_dir)) => Tbb {
+            root,
+            lib_dirs: vec![lib_dir],
+        },
+        _ => panic!("Unable to find oneTBB, please set TBB_ROOT to its install prefix"),
+    }
+}
+
+/// Whether the crate is compiled for x86_64. `cfg!(target_arch)` in a build
+/// script describes the host, which is wrong when cross-compiling.
+fn is_target_x86_64() -> bool {
+    env::var("CARGO_CFG_TARGET_ARCH").unwrap() == "x86_64"
+}
+
+/// Whether the crate is compiled for `os`, see [`is_target_x86_64`].
+fn is_target_os(os: &str) -> bool {
+    env::var("CARGO_CFG_TARGET_OS").unwrap() == os
+}
+
+fn target_isas() -> &'static [&'static str] {
+    if is_target_x86_64() {
+        &["SSE2", "SSE42", "AVX", "AVX2", "AVX512"]
+    } else {
+        &["NEON", "NEON2X"]
+    }
+}
+
+/// ISAs to compile kernels for, taken from `EMBREE_ISA` (comma separated,
+/// e.g. `sse2,avx2,avx512`) or else the `isa-*` features. `None` keeps the
+/// default `EMBREE_MAX_ISA`.
+///
+/// An ISA in `EMBREE_ISA` that the target does not have is an error, while
+/// `isa-*` features for another architecture are ignored with a warning, as
+/// features may be enabled by unrelated crates in the dependency graph.
+fn selected_isas() -> Option<Vec<String>> {
+    println!("cargo:rerun-if-env-changed=EMBREE_ISA");
+    let isas: Vec<String> = match env::var("EMBREE_ISA") {
+        Ok(isa) if !isa.trim().is_empty() => {
+            let isas: Vec<String> = isa
+                .split(',')
+                .map(|isa| isa.trim().to_uppercase())
+                .filter(|isa| !isa.is_empty())
+                .collect();
+            for isa in &isas {
+                assert!(
+                    target_isas().contains(&isa.as_str()),
+                    "Unsupported ISA `{}` in EMBREE_ISA for this target, expected one of {:?}",
+                    isa,
+                    target_isas()
+                );
+            }
+            isas
+        }
+        _ => [
+            (cfg!(feature = "isa-sse2"), "SSE2"),
+            (cfg!(feature = "isa-sse42"), "SSE42"),
+            (cfg!(feature = "isa-avx"), "AVX"),
+            (cfg!(feature = "isa-avx2"), "AVX2"),
+            (cfg!(feature = "isa-avx512"), "AVX512"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .filter(|(_, isa)| {
+            let supported = target_isas().contains(isa);
+            if !supported {
+                println!(
+                    "cargo:warning=Ignoring feature isa-{}, not available on this target",
+                    isa.to_lowercase()
+                );
+            }
+            supported
+        })
+        .map(|(_, isa)| isa.to_string())
+        .collect(),
+    };
+    if isas.is_empty() {
+        None
+    } else {
+        Some(isas)
+    }
+}
+
+fn build_embree(src_dir: &Path, tbb: Option<&Tbb>) -> Result<String> {
     let out_dir = env::var("OUT_DIR").unwrap();
     let generator = env::var("CMAKE_GENERATOR").unwrap_or("Ninja".to_string());
 
-    let mut build = cmake::Config::new("embree");
+    let mut build = cmake::Config::new(src_dir);
     build
         .generator(generator)
         .define("CMAKE_BUILD_TYPE", "Release")
         .define("EMBREE_ISPC_SUPPORT", "OFF")
-        .define("EMBREE_TASKING_SYSTEM", "INTERNAL")
+        .define(
+            "EMBREE_TASKING_SYSTEM",
+            if tbb.is_some() { "TBB" } else { "INTERNAL" },
+        )
         .define("EMBREE_TUTORIALS", "OFF")
         .define("EMBREE_STATIC_LIB", "OFF")
         .define("EMBREE_GEOMETRY_QUAD", "OFF")
         .define("EMBREE_GEOMETRY_CURVE", "ON")
-        .define("EMBREE_GEOMETRY_SUBDIVISION", "OFF")
-        .define("EMBREE_RAY_MASK", "ON")
-        .define("EMBREE_GEOMETRY_POINT", "OFF")
+        .define(
+            "EMBREE_GEOMETRY_SUBDIVISION",
+            if cfg!(feature = "subdivision") {
+                "ON"
+            } else {
+                "OFF"
+            },
+        )
+        .define("EMBREE_GEOMETRY_POINT", "ON")
         .define("CMAKE_MACOSX_RPATH", "ON")
         .define("CMAKE_SKIP_BUILD_RPATH", "OFF")
         .define("CMAKE_BUILD_RPATH_USE_ORIGIN", "ON")
@@ -27,30 +235,41 @@ fn build_embree() -> Result<String> {
         .define("CMAKE_POLICY_VERSION_MINIMUM", "3.5") // workaround CMake 4.0
         .define(
             "CMAKE_INSTALL_RPATH",
-            if cfg!(target_os = "linux") {
+            if is_target_os("linux") {
                 "$ORIGIN"
-            } else if cfg!(target_os = "macos") {
+            } else if is_target_os("macos") {
                 "@loader_path"
             } else {
                 ""
             },
         )
-        .define(
-            "EMBREE_ARM",
-            if cfg!(target_arch = "x86_64") {
-                "OFF"
-            } else {
-                "ON"
-            },
-        )
-        .define(
-            "EMBREE_MAX_ISA",
-            if cfg!(target_arch = "x86_64") {
-                "AVX2"
-            } else {
-                "NEON2X"
-            },
-        );
+        .define("EMBREE_ARM", if is_target_x86_64() { "OFF" } else { "ON" });
+    for (option, enabled, _) in BUILD_OPTIONS {
+        build.define(option, if enabled { "ON" } else { "OFF" });
+    }
+    match selected_isas() {
+        Some(isas) => {
+            // EMBREE_MAX_ISA=NONE makes CMake honour the per-ISA switches, and
+            // embree dispatches between the compiled kernels at runtime.
+            build.define("EMBREE_MAX_ISA", "NONE");
+            for isa in target_isas() {
+                build.define(
+                    format!("EMBREE_ISA_{}", isa),
+                    if isas.iter().any(|i| i == isa) {
+                        "ON"
+                    } else {
+                        "OFF"
+                    },
+                );
+            }
+        }
+        None => {
+            build.define(
+                "EMBREE_MAX_ISA",
+                if is_target_x86_64() { "AVX2" } else { "NEON2X" },
+            );
+        }
+    }
     match env::var("EMBREE_CC") {
         Ok(cc) => {
             build.define("CMAKE_C_COMPILER", cc);
@@ -63,6 +282,10 @@ fn build_embree() -> Result<String> {
         }
         Err(_) => {}
     }
+    if let Some(tbb) = tbb {
+        build.define("EMBREE_TBB_ROOT", &tbb.root);
+        build.define("TBB_ROOT", &tbb.root);
+    }
     build.build();
     Ok(out_dir)
 }
@@ -74,8 +297,8 @@ fn gen(out_dir: &String) -> Result<()> {
     }
     let bindings = bindgen::Builder::default()
         .header(format!("{}/include/embree4/rtcore.h", out_dir))
-        .clang_arg("-I./embree/include")
-        .clang_arg("-I./embree/include/embree4")
+        .clang_arg(format!("-I{}/include", out_dir))
+        .clang_arg(format!("-I{}/include/embree4", out_dir))
         .allowlist_function("rtc.*")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .prepend_enum_name(false)
@@ -108,7 +331,7 @@ fn is_path_dll(path: &PathBuf) -> bool {
     if basic_check {
         return true;
     }
-    if cfg!(target_os = "linux") {
+    if is_target_os("linux") {
         if let Some(stem) = path.file_stem() {
             if let Some(ext) = PathBuf::from(stem).extension() {
                 if ext == "so" {
@@ -121,6 +344,15 @@ fn is_path_dll(path: &PathBuf) -> bool {
 }
 
 fn copy_dlls(src_dir: &PathBuf, dst_dir: &PathBuf) {
+    copy_dlls_if(src_dir, dst_dir, |_| true);
+}
+
+fn is_path_tbb_dll(path: &Path) -> bool {
+    let name = path.file_name().unwrap().to_str().unwrap();
+    name.starts_with("tbb") || name.starts_with("libtbb")
+}
+
+fn copy_dlls_if(src_dir: &Path, dst_dir: &Path, filter: impl Fn(&Path) -> bool) {
     if !src_dir.exists() {
         return;
     }
@@ -129,7 +361,7 @@ fn copy_dlls(src_dir: &PathBuf, dst_dir: &PathBuf) {
     for entry in std::fs::read_dir(out_dir).unwrap() {
         let entry = entry.unwrap();
         let path = entry.path();
-        if is_path_dll(&path) {
+        if is_path_dll(&path) && filter(&path) {
             let copy_if_different = |src, dst| {
                 let p_src = Path::canonicalize(src).unwrap();
                 let p_src = p_src.as_path();
@@ -147,7 +379,7 @@ fn copy_dlls(src_dir: &PathBuf, dst_dir: &PathBuf) {
                 }
             };
             {
-                let dest = dst_dir.clone().join(path.file_name().unwrap());
+                let dest = dst_dir.join(path.file_name().unwrap());
                 copy_if_different(&path, dest);
             }
             // {
@@ -173,125 +405,171 @@ fn prebuild_available() -> bool {
     if force_build {
         return false;
     }
-    if cfg!(target_arch = "x86_64") && (cfg!(target_os = "windows") || cfg!(target_os = "linux")) {
-        true
-    } else {
-        false
+    // The prebuilt packages bundle their own TBB; linking against the
+    // application's oneTBB requires building from source.
+    if cfg!(feature = "tbb") {
+        return false;
     }
+    if BUILD_OPTIONS
+        .iter()
+        .any(|(_, enabled, prebuilt)| enabled != prebuilt)
+    {
+        return false;
+    }
+    is_target_x86_64() && (is_target_os("windows") || is_target_os("linux"))
 }
 
-fn sha256sum(filename: &str) -> String {
-    let mut file = std::fs::File::open(filename).unwrap();
-    let mut hasher = Sha256::new();
-    std::io::copy(&mut file, &mut hasher).unwrap();
-    let hash = hasher.finalize();
-    format!("{:X}", hash)
-}
+const DOWNLOAD_ATTEMPTS: u32 = 4;
 
-fn download_with_curl(url: &str, output: &str, expected_hash: &str) {
-    match env::var("EMBREE_ZIP_FILE") {
-        Ok(path) => {
-            if !path.is_empty() {
-                let hash = sha256sum(&path);
-                assert_eq!(
-                    hash, expected_hash,
-                    "You have downloaded wrong `{}`, expected hash {} but found {}\nPlease download again from {}",
-                    output, expected_hash, hash, url
-                );
-                if &path != output {
-                    std::fs::copy(&path, output).unwrap();
-                }
-                return;
+fn download(url: &str, output: &str, expected_hash: &str) {
+    if let Ok(path) = env::var("EMBREE_ZIP_FILE") {
+        if !path.is_empty() {
+            let hash = sha256sum(&path);
+            assert!(
+                hash.eq_ignore_ascii_case(expected_hash),
+                "You have downloaded wrong `{}`, expected hash {} but found {}\nPlease download again from {}",
+                output, expected_hash, hash, url
+            );
+            if path != output {
+                std::fs::copy(&path, output).unwrap();
             }
+            return;
         }
-        Err(_) => {}
     }
-    eprintln!("Downloading embree...");
-    let mut curl = Command::new("curl")
-        .arg("-L")
-        .arg(url)
-        .arg("--output")
-        .arg(output)
-        .arg("-m")
-        .arg("300")
-        .spawn()
-        .unwrap();
-    let exit_status = curl.wait().unwrap();
-    if !exit_status.success() {
-        panic!("Unable to download embree");
+    println!("cargo:rerun-if-env-changed=EMBREE_DOWNLOAD_MIRROR");
+    let mirror = env::var("EMBREE_DOWNLOAD_MIRROR").ok();
+    let url = download::mirror_url(url, mirror.as_deref());
+    if let Err(error) = download::download_with_retry(
+        &url,
+        output,
+        expected_hash,
+        DOWNLOAD_ATTEMPTS,
+        std::time::Duration::from_secs(1),
+        fetch,
+    ) {
+        panic!("Unable to download embree from {}: {}", url, error);
     }
-    let code = exit_status.code();
-    if let Some(code) = code {
-        if code == 28 {
-            eprintln!("Unable to download embree, timeout");
-            panic!("Unable to download embree, timeout");
-        } else if code != 0 {
-            eprintln!("Unable to download embree, exit code {}", code);
-            panic!("Unable to download embree, exit code {}", code);
-        }
-    } else {
-        eprintln!("Unable to download embree, exit code unknown");
-        panic!("Unable to download embree, exit code unknown");
+}
+
+const EMBREE_VERSION: &str = "4.4.0";
+
+/// Root of the download cache shared by all builds: `EMBREE_CACHE_DIR`, else
+/// `$XDG_CACHE_HOME/embree-sys` (`~/.cache/embree-sys`, or
+/// `%LOCALAPPDATA%\embree-sys` on Windows).
+fn cache_root() -> Option<PathBuf> {
+    for var in ["EMBREE_CACHE_DIR", "XDG_CACHE_HOME", "HOME", "LOCALAPPDATA"] {
+        println!("cargo:rerun-if-env-changed={}", var);
     }
-    let hash = sha256sum(output);
-    assert_eq!(
-        hash.to_lowercase(), expected_hash.to_lowercase(),
-        "File corrupted, expected hash {} for `{}` but found {}",
-        expected_hash, output, hash
-    );
+    if let Ok(dir) = env::var("EMBREE_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let user_cache = if cfg!(target_os = "windows") {
+        env::var("LOCALAPPDATA").ok().map(PathBuf::from)
+    } else {
+        env::var("XDG_CACHE_HOME")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var("HOME")
+                    .ok()
+                    .map(|home| PathBuf::from(home).join(".cache"))
+            })
+    };
+    user_cache.map(|dir| dir.join("embree-sys"))
 }
-fn download_embree() {
-    let linux_url = r#"https://github.com/RenderKit/embree/releases/download/v4.4.0/embree-4.4.0.x86_64.linux.tar.gz"#;
-    let windows_url = r#"https://github.com/RenderKit/embree/releases/download/v4.4.0/embree-4.4.0.x64.windows.zip"#;
-    let source_url = r#"https://github.com/RenderKit/embree/archive/refs/tags/v4.4.0.tar.gz"#;
-    let out_dir = "embree";
-    if prebuild_available() {
-        let url = if cfg!(target_os = "windows") {
-            windows_url
-        } else {
-            linux_url
-        };
-        let filename = if cfg!(target_os = "windows") {
-            "embree.zip"
-        } else {
-            "embree.tar.gz"
-        };
-        let hash = if cfg!(target_os = "windows") {
-            "d951e5e6bd295c54cdd66be9cdb44a4e8c42fb38a99f94f79305e48765fc3454"
-        } else {
-            "cb3d4402537fc9165c76c3316b8953dcfea523cd1eaf588e2de7639864ee3c57"
-        };
-        download_with_curl(url, filename, hash);
-        std::fs::create_dir_all(&out_dir).unwrap();
-        if cfg!(target_os = "windows") {
-            Command::new("tar")
-                .args(["-zxvf", filename, "-C", &out_dir])
-                .output()
-                .unwrap();
+
+/// Downloads and extracts embree into `<cache>/<version>/<hash>/embree`,
+/// falling back to `OUT_DIR` when the cache is not writable, and returns the
+/// extracted tree. Concurrent builds serialize on a lock file in that
+/// directory, so only the first one downloads.
+fn download_embree() -> PathBuf {
+    let linux_url = format!(
+        "{0}/releases/download/v{1}/embree-{1}.x86_64.linux.tar.gz",
+        EMBREE_REPO_URL, EMBREE_VERSION
+    );
+    let windows_url = format!(
+        "{0}/releases/download/v{1}/embree-{1}.x64.windows.zip",
+        EMBREE_REPO_URL, EMBREE_VERSION
+    );
+    let source_url = format!(
+        "{}/archive/refs/tags/v{}.tar.gz",
+        EMBREE_REPO_URL, EMBREE_VERSION
+    );
+    let prebuilt = prebuild_available();
+    let (url, filename, hash) = if prebuilt {
+        if is_target_os("windows") {
+            (
+                windows_url,
+                "embree.zip",
+                "d951e5e6bd295c54cdd66be9cdb44a4e8c42fb38a99f94f79305e48765fc3454",
+            )
         } else {
-            Command::new("tar")
-                .args(["-zxvf", filename, "-C", &out_dir])
-                .output()
-                .unwrap();
+            (
+                linux_url,
+                "embree.tar.gz",
+                "cb3d4402537fc9165c76c3316b8953dcfea523cd1eaf588e2de7639864ee3c57",
+            )
         }
     } else {
-        download_with_curl(
+        (
             source_url,
             "embree.tar.gz",
             "acb517b0ea0f4b442235d5331b69f96192c28da6aca5d5dde0cbe40799638d5c",
+        )
+    };
+    let key = PathBuf::from(EMBREE_VERSION).join(&hash[..16]);
+    let cache_dir = cache_root().map(|root| root.join(&key));
+    // A finished extraction is never modified again, so it is used without
+    // taking the lock, which also works for read-only caches.
+    if let Some(cache_dir) = &cache_dir {
+        let complete = cache_dir.join(".complete");
+        if complete.exists() {
+            println!("cargo:rerun-if-changed={}", complete.display());
+            return cache_dir.join("embree");
+        }
+    }
+    let open_lock = |dir: &Path| -> Result<fs::File> {
+        fs::create_dir_all(dir)?;
+        fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(".lock"))
+    };
+    let (cache_dir, lock) = cache_dir
+        .and_then(|dir| open_lock(&dir).ok().map(|lock| (dir, lock)))
+        .unwrap_or_else(|| {
+            let dir = PathBuf::from(env::var("OUT_DIR").unwrap()).join(&key);
+            let lock = open_lock(&dir).unwrap();
+            (dir, lock)
+        });
+    let out_dir = cache_dir.join("embree");
+    FileExt::lock(&lock).unwrap();
+    let complete = cache_dir.join(".complete");
+    if !complete.exists() {
+        let archive = cache_dir.join(filename);
+        let archive = archive.to_str().unwrap();
+        download(&url, archive, hash);
+        fs::create_dir_all(&out_dir).unwrap();
+        let out_dir = out_dir.to_str().unwrap();
+        let mut args = vec!["-zxvf", archive, "-C", out_dir];
+        if !prebuilt {
+            args.push("--strip-components=1");
+        }
+        let output = Command::new("tar").args(args).output().unwrap();
+        assert!(
+            output.status.success(),
+            "Unable to extract `{}`: {}",
+            archive,
+            String::from_utf8_lossy(&output.stderr)
         );
-        std::fs::create_dir_all(&out_dir).unwrap();
-        Command::new("tar")
-            .args([
-                "-zxvf",
-                "embree.tar.gz",
-                "-C",
-                &out_dir,
-                "--strip-components=1",
-            ])
-            .output()
-            .unwrap();
+        fs::remove_file(archive).unwrap();
+        fs::write(&complete, "").unwrap();
     }
+    FileExt::unlock(&lock).unwrap();
+    // Deleting the cache removes the stamp and re-runs the build script.
+    println!("cargo:rerun-if-changed={}", complete.display());
+    out_dir
 }
 
 fn get_out_dir() -> Option<String> {
@@ -299,14 +577,92 @@ fn get_out_dir() -> Option<String> {
     env::var("EMBREE_DLL_OUT_DIR").ok()
 }
 
-fn build_embree_from_source() -> Result<()> {
-    let out_dir = build_embree()?;
+/// Embree sources used by the `vendored` feature, a checkout of the
+/// `v{EMBREE_VERSION}` tag given by `EMBREE_SRC_DIR`.
+fn vendored_source_dir() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=EMBREE_SRC_DIR");
+    let src_dir = match env::var("EMBREE_SRC_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => panic!(
+            "The vendored feature requires EMBREE_SRC_DIR to point to the embree v{} sources",
+            EMBREE_VERSION
+        ),
+    };
+    assert!(
+        src_dir.join("CMakeLists.txt").exists(),
+        "Embree sources not found in EMBREE_SRC_DIR `{}`",
+        src_dir.display()
+    );
+    // src/binding.rs is generated for EMBREE_VERSION, other releases may
+    // differ in struct layouts.
+    let version = source_tree_version(&src_dir).unwrap_or_else(|| {
+        panic!(
+            "Unable to read the embree version from `{}`",
+            src_dir.join("CMakeLists.txt").display()
+        )
+    });
+    assert_eq!(
+        version,
+        EMBREE_VERSION,
+        "Embree sources in `{}` are version {}, but the bindings are generated for {}",
+        src_dir.display(),
+        version,
+        EMBREE_VERSION
+    );
+    println!("cargo:rerun-if-changed={}", src_dir.display());
+    src_dir
+}
+
+/// Reads `major.minor.patch` from the `SET(EMBREE_VERSION_*)` lines of
+/// embree's top-level CMakeLists.txt.
+fn source_tree_version(src_dir: &Path) -> Option<String> {
+    let cmake = fs::read_to_string(src_dir.join("CMakeLists.txt")).ok()?;
+    let component = |name: &str| {
+        cmake.lines().find_map(|line| {
+            let line = line.trim();
+            if !line.get(..3)?.eq_ignore_ascii_case("set") {
+                return None;
+            }
+            let args = line[3..].trim_start().strip_prefix('(')?;
+            let mut args = args.trim_end().trim_end_matches(')').split_whitespace();
+            if args.next()? == name {
+                args.next().map(|value| value.trim_matches('"').to_string())
+            } else {
+                None
+            }
+        })
+    };
+    Some(format!(
+        "{}.{}.{}",
+        component("EMBREE_VERSION_MAJOR")?,
+        component("EMBREE_VERSION_MINOR")?,
+        component("EMBREE_VERSION_PATCH")?
+    ))
+}
+
+fn build_embree_from_source(src_dir: &Path) -> Result<()> {
+    let tbb = if cfg!(feature = "tbb") {
+        Some(find_tbb())
+    } else {
+        None
+    };
+    let out_dir = build_embree(src_dir, tbb.as_ref())?;
     gen(&out_dir)?;
     let out_dir = env::var("OUT_DIR").unwrap();
     println!("cargo:rustc-link-search=native={}/bin/", out_dir);
     println!("cargo:rustc-link-search=native={}/lib/", out_dir);
     println!("cargo:rustc-link-search=native={}/lib64/", out_dir);
     println!("cargo:rustc-link-lib=dylib=embree4");
+    if let Some(tbb) = &tbb {
+        for lib_dir in &tbb.lib_dirs {
+            println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        }
+        if is_target_os("windows") {
+            println!("cargo:rustc-link-lib=dylib=tbb12");
+        } else {
+            println!("cargo:rustc-link-lib=dylib=tbb");
+        }
+    }
     let out_dir = PathBuf::from(out_dir);
     if let Some(dst_dir) = get_out_dir() {
         dbg!(&dst_dir);
@@ -320,26 +676,33 @@ fn build_embree_from_source() -> Result<()> {
         };
         copy_dlls(&get_dll_dir("lib"), &dst_dir);
         copy_dlls(&get_dll_dir("lib64"), &dst_dir);
+        if let Some(tbb) = &tbb {
+            for lib_dir in &tbb.lib_dirs {
+                copy_dlls_if(lib_dir, &dst_dir, is_path_tbb_dll);
+            }
+        }
     }
     Ok(())
 }
 
-fn prebuild() -> Result<()> {
-    gen(&"embree".to_string())?;
-    let cur_file = fs::canonicalize(file!())?;
-    let current_dir = cur_file.parent().unwrap();
+fn prebuild(embree_dir: &Path) -> Result<()> {
+    if selected_isas().is_some() {
+        println!("cargo:warning=EMBREE_ISA and the isa-* features only apply when building embree from source, set EMBREE_FORCE_BUILD_FROM_SOURCE=1");
+    }
+    let embree_dir = fs::canonicalize(embree_dir)?;
+    gen(&embree_dir.display().to_string())?;
     println!(
-        "cargo:rustc-link-search=native={}/embree/bin/",
-        current_dir.display()
+        "cargo:rustc-link-search=native={}",
+        embree_dir.join("bin").display()
     );
     println!(
-        "cargo:rustc-link-search=native={}/embree/lib/",
-        current_dir.display()
+        "cargo:rustc-link-search=native={}",
+        embree_dir.join("lib").display()
     );
     println!("cargo:rustc-link-lib=dylib=embree4");
 
     let get_dll_dir = |subdir: &str| {
-        let dll_dir = PathBuf::from("embree").join(subdir);
+        let dll_dir = embree_dir.join(subdir);
         assert!(dll_dir.exists());
         fs::canonicalize(dll_dir).unwrap()
     };
@@ -350,7 +713,7 @@ fn prebuild() -> Result<()> {
         assert!(dst_dir.exists());
         let dst_dir = fs::canonicalize(dst_dir).unwrap();
         copy_dlls(&get_dll_dir("lib"), &dst_dir);
-        if cfg!(target_os = "windows") {
+        if is_target_os("windows") {
             copy_dlls(&get_dll_dir("bin"), &dst_dir);
         }
     }
@@ -359,11 +722,15 @@ fn prebuild() -> Result<()> {
 
 fn main() -> Result<()> {
     println!("cargo:rerun-if-env-changed=EMBREE_ZIP_FILE");
-    download_embree();
+    write_build_config();
+    if cfg!(feature = "vendored") {
+        return build_embree_from_source(&vendored_source_dir());
+    }
+    let embree_dir = download_embree();
     if prebuild_available() {
-        prebuild()?;
+        prebuild(&embree_dir)?;
     } else {
-        build_embree_from_source()?;
+        build_embree_from_source(&embree_dir)?;
     }
     Ok(())
 }
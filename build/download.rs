@@ -0,0 +1,109 @@
+// Download logic of the build script, kept in its own file so that
+// tests/download.rs can include it.
+use sha2::{Digest, Sha256};
+use std::io;
+use std::time::Duration;
+
+pub const EMBREE_REPO_URL: &str = "https://github.com/RenderKit/embree";
+
+/// Rewrites a GitHub URL of the embree repository onto `mirror`, which must
+/// serve the same paths (`/releases/download/...` and `/archive/refs/tags/...`).
+pub fn mirror_url(url: &str, mirror: Option<&str>) -> String {
+    match mirror {
+        Some(mirror) if !mirror.is_empty() => {
+            url.replacen(EMBREE_REPO_URL, mirror.trim_end_matches('/'), 1)
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Calls `fetch(url, output)`, which returns the SHA-256 of what it wrote,
+/// until that hash matches `expected_hash`. Gives up after `attempts` tries,
+/// waiting `delay` before the first retry and doubling it each time.
+pub fn download_with_retry(
+    url: &str,
+    output: &str,
+    expected_hash: &str,
+    attempts: u32,
+    mut delay: Duration,
+    mut fetch: impl FnMut(&str, &str) -> io::Result<String>,
+) -> Result<(), String> {
+    let mut error = String::from("no download attempted");
+    for attempt in 1..=attempts {
+        eprintln!(
+            "Downloading embree from {} (attempt {}/{})...",
+            url, attempt, attempts
+        );
+        error = match fetch(url, output) {
+            Ok(hash) if hash.eq_ignore_ascii_case(expected_hash) => return Ok(()),
+            Ok(hash) => format!(
+                "file corrupted, expected hash {} for `{}` but found {}",
+                expected_hash, output, hash
+            ),
+            Err(err) => err.to_string(),
+        };
+        if attempt < attempts {
+            eprintln!(
+                "Unable to download embree: {}, retrying in {:?}",
+                error, delay
+            );
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+    Err(error)
+}
+
+pub fn sha256sum(filename: &str) -> String {
+    let mut file = std::fs::File::open(filename).unwrap();
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).unwrap();
+    let hash = hasher.finalize();
+    format!("{:X}", hash)
+}
+
+/// Fetches `url` into `output` with curl and returns the SHA-256 of the file.
+#[cfg(not(feature = "download"))]
+pub fn fetch(url: &str, output: &str) -> io::Result<String> {
+    let status = std::process::Command::new("curl")
+        .arg("-L")
+        .arg("--fail")
+        .arg(url)
+        .arg("--output")
+        .arg(output)
+        .arg("-m")
+        .arg("300")
+        .status()?;
+    match status.code() {
+        Some(0) => Ok(sha256sum(output)),
+        Some(28) => Err(io::Error::other("curl timed out")),
+        Some(code) => Err(io::Error::other(format!("curl exit code {}", code))),
+        None => Err(io::Error::other("curl exit code unknown")),
+    }
+}
+
+/// Fetches `url` into `output` in-process, hashing the body while it is
+/// written. Proxies are taken from `ALL_PROXY`/`HTTPS_PROXY`/`HTTP_PROXY`,
+/// honouring `NO_PROXY`.
+#[cfg(feature = "download")]
+pub fn fetch(url: &str, output: &str) -> io::Result<String> {
+    use std::io::{Read, Write};
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(300)))
+        .build()
+        .into();
+    let response = agent.get(url).call().map_err(io::Error::other)?;
+    let mut reader = response.into_body().into_reader();
+    let mut file = std::fs::File::create(output)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n])?;
+    }
+    Ok(format!("{:X}", hasher.finalize()))
+}
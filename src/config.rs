@@ -0,0 +1,19 @@
+/// Embree compile-time options the linked library was configured with,
+/// selected through this crate's cargo features.
+///
+/// The options that embree reports back can be checked at runtime against
+/// `rtcGetDeviceProperty`, e.g. `backface_culling` against
+/// `RTC_DEVICE_PROPERTY_BACKFACE_CULLING_ENABLED`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BuildConfig {
+    pub backface_culling: bool,
+    pub backface_culling_curves: bool,
+    pub filter_function: bool,
+    pub ignore_invalid_rays: bool,
+    pub compact_polys: bool,
+    pub min_width: bool,
+    pub ray_packets: bool,
+    pub ray_mask: bool,
+}
+
+include!(concat!(env!("OUT_DIR"), "/build_config.rs"));
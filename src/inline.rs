@@ -0,0 +1,301 @@
+// Hand-written counterparts of the `RTC_FORCEINLINE` helpers and `#define`
+// constants in the embree4 headers. bindgen only emits exported symbols and
+// the types they use, so these have to live here.
+use crate::*;
+
+/// Invalid geometry/instance ID (`RTC_INVALID_GEOMETRY_ID` in `rtcore_common.h`).
+pub const RTC_INVALID_GEOMETRY_ID: ::std::os::raw::c_uint = !0;
+
+/// Maximum instance nesting depth the bindings were generated for; it sizes
+/// the `instID` arrays of [`RTCPointQueryContext`] and the hit structures.
+pub const RTC_MAX_INSTANCE_LEVEL_COUNT: ::std::os::raw::c_uint = 1;
+
+/// Maximum number of time steps of a motion-blurred geometry
+/// (`RTC_MAX_TIME_STEP_COUNT` in `rtcore_common.h`).
+pub const RTC_MAX_TIME_STEP_COUNT: ::std::os::raw::c_uint = 129;
+
+/// Largest `maxLeafSize` accepted by [`rtcBuildBVH`]
+/// (`RTC_BUILD_MAX_PRIMITIVES_PER_LEAF` in `rtcore_builder.h`).
+pub const RTC_BUILD_MAX_PRIMITIVES_PER_LEAF: ::std::os::raw::c_uint = 32;
+
+/// Per-segment flags stored in the `RTC_BUFFER_TYPE_FLAGS` buffer of linear
+/// curves, telling embree whether the segment has a left/right neighbor.
+pub type RTCCurveFlags = ::std::os::raw::c_uint;
+pub const RTC_CURVE_FLAG_NEIGHBOR_LEFT: RTCCurveFlags = 1;
+pub const RTC_CURVE_FLAG_NEIGHBOR_RIGHT: RTCCurveFlags = 2;
+
+/// One record of the `RTC_BUFFER_TYPE_GRID` buffer (`RTC_FORMAT_GRID`) of a
+/// grid geometry. The grid covers `width * height` vertices starting at
+/// `startVertexID`, with rows `stride` vertices apart.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct RTCGrid {
+    pub startVertexID: ::std::os::raw::c_uint,
+    pub stride: ::std::os::raw::c_uint,
+    pub width: ::std::os::raw::c_ushort,
+    pub height: ::std::os::raw::c_ushort,
+}
+
+/// Port of `rtcInitQuaternionDecomposition` from `rtcore_quaternion.h`.
+///
+/// Sets `qd` to the identity transform: unit scale and rotation, no skew,
+/// shift or translation.
+///
+/// # Safety
+/// `qd` must be valid for writes; it may point to uninitialized memory.
+#[inline]
+pub unsafe fn rtcInitQuaternionDecomposition(qd: *mut RTCQuaternionDecomposition) {
+    qd.write(RTCQuaternionDecomposition {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        scale_z: 1.0,
+        skew_xy: 0.0,
+        skew_xz: 0.0,
+        skew_yz: 0.0,
+        shift_x: 0.0,
+        shift_y: 0.0,
+        shift_z: 0.0,
+        quaternion_r: 1.0,
+        quaternion_i: 0.0,
+        quaternion_j: 0.0,
+        quaternion_k: 0.0,
+        translation_x: 0.0,
+        translation_y: 0.0,
+        translation_z: 0.0,
+    });
+}
+
+/// Port of `rtcQuaternionDecompositionSetQuaternion`.
+///
+/// # Safety
+/// `qd` must be valid for writes.
+#[inline]
+pub unsafe fn rtcQuaternionDecompositionSetQuaternion(
+    qd: *mut RTCQuaternionDecomposition,
+    r: f32,
+    i: f32,
+    j: f32,
+    k: f32,
+) {
+    ::std::ptr::addr_of_mut!((*qd).quaternion_r).write(r);
+    ::std::ptr::addr_of_mut!((*qd).quaternion_i).write(i);
+    ::std::ptr::addr_of_mut!((*qd).quaternion_j).write(j);
+    ::std::ptr::addr_of_mut!((*qd).quaternion_k).write(k);
+}
+
+/// Port of `rtcQuaternionDecompositionSetScale`.
+///
+/// # Safety
+/// `qd` must be valid for writes.
+#[inline]
+pub unsafe fn rtcQuaternionDecompositionSetScale(
+    qd: *mut RTCQuaternionDecomposition,
+    scale_x: f32,
+    scale_y: f32,
+    scale_z: f32,
+) {
+    ::std::ptr::addr_of_mut!((*qd).scale_x).write(scale_x);
+    ::std::ptr::addr_of_mut!((*qd).scale_y).write(scale_y);
+    ::std::ptr::addr_of_mut!((*qd).scale_z).write(scale_z);
+}
+
+/// Port of `rtcQuaternionDecompositionSetSkew`.
+///
+/// # Safety
+/// `qd` must be valid for writes.
+#[inline]
+pub unsafe fn rtcQuaternionDecompositionSetSkew(
+    qd: *mut RTCQuaternionDecomposition,
+    skew_xy: f32,
+    skew_xz: f32,
+    skew_yz: f32,
+) {
+    ::std::ptr::addr_of_mut!((*qd).skew_xy).write(skew_xy);
+    ::std::ptr::addr_of_mut!((*qd).skew_xz).write(skew_xz);
+    ::std::ptr::addr_of_mut!((*qd).skew_yz).write(skew_yz);
+}
+
+/// Port of `rtcQuaternionDecompositionSetShift`.
+///
+/// # Safety
+/// `qd` must be valid for writes.
+#[inline]
+pub unsafe fn rtcQuaternionDecompositionSetShift(
+    qd: *mut RTCQuaternionDecomposition,
+    shift_x: f32,
+    shift_y: f32,
+    shift_z: f32,
+) {
+    ::std::ptr::addr_of_mut!((*qd).shift_x).write(shift_x);
+    ::std::ptr::addr_of_mut!((*qd).shift_y).write(shift_y);
+    ::std::ptr::addr_of_mut!((*qd).shift_z).write(shift_z);
+}
+
+/// Port of `rtcQuaternionDecompositionSetTranslation`.
+///
+/// # Safety
+/// `qd` must be valid for writes.
+#[inline]
+pub unsafe fn rtcQuaternionDecompositionSetTranslation(
+    qd: *mut RTCQuaternionDecomposition,
+    translation_x: f32,
+    translation_y: f32,
+    translation_z: f32,
+) {
+    ::std::ptr::addr_of_mut!((*qd).translation_x).write(translation_x);
+    ::std::ptr::addr_of_mut!((*qd).translation_y).write(translation_y);
+    ::std::ptr::addr_of_mut!((*qd).translation_z).write(translation_z);
+}
+
+/// Port of `rtcInterpolate0` from `rtcore_geometry.h`: interpolates only the
+/// value `P`, without derivatives.
+///
+/// # Safety
+/// Same requirements as [`rtcInterpolate`]; `P` must hold `valueCount` floats.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn rtcInterpolate0(
+    geometry: RTCGeometry,
+    primID: ::std::os::raw::c_uint,
+    u: f32,
+    v: f32,
+    bufferType: RTCBufferType,
+    bufferSlot: ::std::os::raw::c_uint,
+    P: *mut f32,
+    valueCount: ::std::os::raw::c_uint,
+) {
+    rtcInterpolate2(
+        geometry,
+        primID,
+        u,
+        v,
+        bufferType,
+        bufferSlot,
+        P,
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        valueCount,
+    );
+}
+
+/// Port of `rtcInterpolate1`: interpolates `P` and its first derivatives.
+///
+/// # Safety
+/// Same requirements as [`rtcInterpolate`]; every non-null output must hold
+/// `valueCount` floats.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn rtcInterpolate1(
+    geometry: RTCGeometry,
+    primID: ::std::os::raw::c_uint,
+    u: f32,
+    v: f32,
+    bufferType: RTCBufferType,
+    bufferSlot: ::std::os::raw::c_uint,
+    P: *mut f32,
+    dPdu: *mut f32,
+    dPdv: *mut f32,
+    valueCount: ::std::os::raw::c_uint,
+) {
+    rtcInterpolate2(
+        geometry,
+        primID,
+        u,
+        v,
+        bufferType,
+        bufferSlot,
+        P,
+        dPdu,
+        dPdv,
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        ::std::ptr::null_mut(),
+        valueCount,
+    );
+}
+
+/// Port of `rtcInterpolate2`: interpolates `P` and its first and second
+/// derivatives.
+///
+/// # Safety
+/// Same requirements as [`rtcInterpolate`]; every non-null output must hold
+/// `valueCount` floats.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn rtcInterpolate2(
+    geometry: RTCGeometry,
+    primID: ::std::os::raw::c_uint,
+    u: f32,
+    v: f32,
+    bufferType: RTCBufferType,
+    bufferSlot: ::std::os::raw::c_uint,
+    P: *mut f32,
+    dPdu: *mut f32,
+    dPdv: *mut f32,
+    ddPdudu: *mut f32,
+    ddPdvdv: *mut f32,
+    ddPdudv: *mut f32,
+    valueCount: ::std::os::raw::c_uint,
+) {
+    let args = RTCInterpolateArguments {
+        geometry,
+        primID,
+        u,
+        v,
+        bufferType,
+        bufferSlot,
+        P,
+        dPdu,
+        dPdv,
+        ddPdudu,
+        ddPdvdv,
+        ddPdudv,
+        valueCount,
+    };
+    rtcInterpolate(&args);
+}
+
+/// Port of `rtcInitPointQueryContext` from `rtcore_common.h`: empties the
+/// instance stack before a call to [`rtcPointQuery`].
+///
+/// # Safety
+/// `context` must be valid for writes; it may point to uninitialized memory.
+#[inline]
+pub unsafe fn rtcInitPointQueryContext(context: *mut RTCPointQueryContext) {
+    ::std::ptr::addr_of_mut!((*context).instStackSize).write(0);
+    ::std::ptr::addr_of_mut!((*context).instID)
+        .cast::<::std::os::raw::c_uint>()
+        .write(RTC_INVALID_GEOMETRY_ID);
+}
+
+/// Port of `rtcDefaultBuildArguments` from `rtcore_builder.h`: medium quality
+/// binary BVH with no primitives and no callbacks set.
+#[inline]
+pub fn rtcDefaultBuildArguments() -> RTCBuildArguments {
+    RTCBuildArguments {
+        byteSize: ::std::mem::size_of::<RTCBuildArguments>(),
+        buildQuality: RTC_BUILD_QUALITY_MEDIUM,
+        buildFlags: RTC_BUILD_FLAG_NONE,
+        maxBranchingFactor: 2,
+        maxDepth: 32,
+        sahBlockSize: 1,
+        minLeafSize: 1,
+        maxLeafSize: RTC_BUILD_MAX_PRIMITIVES_PER_LEAF,
+        traversalCost: 1.0,
+        intersectionCost: 1.0,
+        bvh: ::std::ptr::null_mut(),
+        primitives: ::std::ptr::null_mut(),
+        primitiveCount: 0,
+        primitiveArrayCapacity: 0,
+        createNode: None,
+        setNodeChildren: None,
+        setNodeBounds: None,
+        createLeaf: None,
+        splitPrimitive: None,
+        buildProgress: None,
+        userPtr: ::std::ptr::null_mut(),
+    }
+}
@@ -3,9 +3,68 @@
 #![allow(non_camel_case_types)]
 include!("binding.rs");
 
+mod config;
+mod inline;
+pub use config::*;
+pub use inline::*;
+
 mod test {
     #[test]
     fn new_device() {
         unsafe { crate::rtcNewDevice(std::ptr::null()); }
     }
+    #[test]
+    fn build_config_matches_device() {
+        use crate::*;
+        unsafe {
+            let device = rtcNewDevice(std::ptr::null());
+            let property = |p| rtcGetDeviceProperty(device, p) != 0;
+            let config = BUILD_CONFIG;
+            assert_eq!(property(RTC_DEVICE_PROPERTY_BACKFACE_CULLING_ENABLED), config.backface_culling);
+            assert_eq!(property(RTC_DEVICE_PROPERTY_BACKFACE_CULLING_CURVES_ENABLED), config.backface_culling_curves);
+            assert_eq!(property(RTC_DEVICE_PROPERTY_FILTER_FUNCTION_SUPPORTED), config.filter_function);
+            assert_eq!(property(RTC_DEVICE_PROPERTY_IGNORE_INVALID_RAYS_ENABLED), config.ignore_invalid_rays);
+            assert_eq!(property(RTC_DEVICE_PROPERTY_COMPACT_POLYS_ENABLED), config.compact_polys);
+            assert_eq!(property(RTC_DEVICE_PROPERTY_RAY_MASK_SUPPORTED), config.ray_mask);
+            rtcReleaseDevice(device);
+        }
+    }
+    #[test]
+    fn init_quaternion_decomposition() {
+        let mut qd = std::mem::MaybeUninit::<crate::RTCQuaternionDecomposition>::uninit();
+        let qd = unsafe {
+            crate::rtcInitQuaternionDecomposition(qd.as_mut_ptr());
+            crate::rtcQuaternionDecompositionSetTranslation(qd.as_mut_ptr(), 1.0, 2.0, 3.0);
+            qd.assume_init()
+        };
+        assert_eq!((qd.scale_x, qd.scale_y, qd.scale_z), (1.0, 1.0, 1.0));
+        assert_eq!(qd.quaternion_r, 1.0);
+        assert_eq!((qd.quaternion_i, qd.quaternion_j, qd.quaternion_k), (0.0, 0.0, 0.0));
+        assert_eq!(
+            (qd.translation_x, qd.translation_y, qd.translation_z),
+            (1.0, 2.0, 3.0)
+        );
+    }
+    #[test]
+    fn init_point_query_context() {
+        let mut context = std::mem::MaybeUninit::<crate::RTCPointQueryContext>::uninit();
+        unsafe {
+            crate::rtcInitPointQueryContext(context.as_mut_ptr());
+            let context = context.as_ptr();
+            assert_eq!((*context).instStackSize, 0);
+            assert_eq!((*context).instID[0], crate::RTC_INVALID_GEOMETRY_ID);
+        }
+    }
+    #[test]
+    fn default_build_arguments() {
+        let args = crate::rtcDefaultBuildArguments();
+        assert_eq!(args.byteSize, std::mem::size_of::<crate::RTCBuildArguments>());
+        assert_eq!(args.maxBranchingFactor, 2);
+        assert!(args.createNode.is_none() && args.primitives.is_null());
+    }
+    #[test]
+    fn grid_layout() {
+        assert_eq!(std::mem::size_of::<crate::RTCGrid>(), 12);
+        assert_eq!(std::mem::align_of::<crate::RTCGrid>(), 4);
+    }
 }
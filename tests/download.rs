@@ -0,0 +1,148 @@
+// Only part of the build script's helpers are used here.
+#[allow(dead_code)]
+#[path = "../build/download.rs"]
+mod download;
+
+use download::{download_with_retry, mirror_url, EMBREE_REPO_URL};
+use std::io;
+use std::time::Duration;
+
+const URL: &str =
+    "https://github.com/RenderKit/embree/releases/download/v4.4.0/embree-4.4.0.x86_64.linux.tar.gz";
+
+#[test]
+fn mirror_url_rewrites_repo_prefix() {
+    assert_eq!(
+        mirror_url(URL, Some("http://127.0.0.1:8000/embree/")),
+        "http://127.0.0.1:8000/embree/releases/download/v4.4.0/embree-4.4.0.x86_64.linux.tar.gz"
+    );
+    assert_eq!(mirror_url(URL, None), URL);
+    assert_eq!(mirror_url(URL, Some("")), URL);
+    assert!(URL.starts_with(EMBREE_REPO_URL));
+}
+
+#[test]
+fn retries_after_hash_mismatch() {
+    let mut calls = 0;
+    let result = download_with_retry(URL, "out", "abcd", 4, Duration::ZERO, |url, output| {
+        calls += 1;
+        assert_eq!((url, output), (URL, "out"));
+        Ok(if calls == 1 { "0000" } else { "ABCD" }.to_string())
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(calls, 2);
+}
+
+#[test]
+fn gives_up_after_all_attempts() {
+    let mut calls = 0;
+    let result = download_with_retry(URL, "out", "abcd", 3, Duration::ZERO, |_, _| {
+        calls += 1;
+        Err(io::Error::other("connection refused"))
+    });
+    assert_eq!(result, Err("connection refused".to_string()));
+    assert_eq!(calls, 3);
+}
+
+/// Runs the real `fetch` against a local HTTP server standing in for GitHub.
+#[cfg(feature = "download")]
+mod http {
+    use super::download::{download_with_retry, fetch, mirror_url};
+    use super::URL;
+    use sha2::{Digest, Sha256};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const PATH: &str = "/releases/download/v4.4.0/embree-4.4.0.x86_64.linux.tar.gz";
+    const BODY: &[u8] = b"\x1f\x8b not really a gzip stream";
+
+    /// A response as `(status line, extra header lines, body)`.
+    type Response = (&'static str, String, Vec<u8>);
+
+    /// Serves `respond(path)` on a local port until the test exits and returns
+    /// the server's base URL.
+    fn serve(respond: impl Fn(&str) -> Response + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, headers, body) = respond(path);
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+                    status,
+                    body.len(),
+                    headers
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        base
+    }
+
+    fn output(name: &str) -> String {
+        let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn hash(body: &[u8]) -> String {
+        format!("{:X}", Sha256::digest(body))
+    }
+
+    #[test]
+    fn fetch_follows_redirect_and_hashes_raw_body() {
+        let base = serve(|path| match path {
+            PATH => ("302 Found", "Location: /asset\r\n".to_string(), vec![]),
+            // The archive must be hashed as served, not transparently gunzipped.
+            "/asset" => (
+                "200 OK",
+                "Content-Encoding: gzip\r\n".to_string(),
+                BODY.to_vec(),
+            ),
+            _ => ("404 Not Found", String::new(), vec![]),
+        });
+        let output = output("redirect.tar.gz");
+        let found = fetch(&mirror_url(URL, Some(&base)), &output).unwrap();
+        assert_eq!(found, hash(BODY));
+        assert_eq!(std::fs::read(&output).unwrap(), BODY);
+    }
+
+    #[test]
+    fn fetch_fails_on_http_error() {
+        let base = serve(|_| ("404 Not Found", String::new(), b"missing".to_vec()));
+        assert!(fetch(&mirror_url(URL, Some(&base)), &output("missing.tar.gz")).is_err());
+    }
+
+    #[test]
+    fn retries_corrupted_download_over_http() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base = serve(move |_| {
+            let body = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                &BODY[..4]
+            } else {
+                BODY
+            };
+            ("200 OK", String::new(), body.to_vec())
+        });
+        let output = output("retry.tar.gz");
+        let url = mirror_url(URL, Some(&base));
+        let result = download_with_retry(&url, &output, &hash(BODY), 3, Duration::ZERO, fetch);
+        assert_eq!(result, Ok(()));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read(&output).unwrap(), BODY);
+    }
+}